@@ -8,14 +8,37 @@ use crate::v2::{
 };
 use crate::{Error, JsonRawValue, TEN_MB_SIZE_BYTES};
 use async_trait::async_trait;
+use base64::engine::{general_purpose, Engine as _};
 use fnv::FnvHashMap;
+use http::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use rand::Rng;
 use serde::de::DeserializeOwned;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Default request timeout, matching most production RPC clients' expectations when fanning
+/// out calls to flaky upstreams.
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Upper bound on the backoff delay between retries, regardless of how many attempts have
+/// already been made.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Opt-in retry policy for transient transport failures, applied by [`HttpClient::request`] and
+/// [`HttpClient::batch_request`].
+#[derive(Debug, Clone, Copy)]
+struct RetryPolicy {
+	max_retries: u32,
+	base_delay: Duration,
+}
 
 /// Http Client Builder.
 #[derive(Debug)]
 pub struct HttpClientBuilder {
 	max_request_body_size: u32,
+	headers: HeaderMap,
+	request_timeout: Duration,
+	retry_policy: Option<RetryPolicy>,
 }
 
 impl HttpClientBuilder {
@@ -25,17 +48,77 @@ impl HttpClientBuilder {
 		self
 	}
 
+	/// Sets custom headers to be sent with every request, e.g. an API gateway token or a
+	/// tracing header. These are merged into the headers jsonrpsee sets internally
+	/// (`content-type` etc.), with `headers` taking precedence on conflicts.
+	pub fn set_headers(mut self, headers: HeaderMap) -> Self {
+		self.headers = headers;
+		self
+	}
+
+	/// Authenticate every request with a bearer token, setting the `Authorization` header.
+	///
+	/// Fails if `token` doesn't round-trip through a HTTP header value (e.g. contains a
+	/// newline or other control character).
+	pub fn bearer_auth(self, token: impl AsRef<str>) -> Result<Self, Error> {
+		self.auth_header(format!("Bearer {}", token.as_ref()))
+	}
+
+	/// Authenticate every request with HTTP basic auth, setting the `Authorization` header.
+	///
+	/// Fails if the encoded credentials don't round-trip through a HTTP header value.
+	pub fn basic_auth(self, username: impl AsRef<str>, password: Option<impl AsRef<str>>) -> Result<Self, Error> {
+		let credentials = match password {
+			Some(password) => format!("{}:{}", username.as_ref(), password.as_ref()),
+			None => format!("{}:", username.as_ref()),
+		};
+		self.auth_header(format!("Basic {}", general_purpose::STANDARD.encode(credentials)))
+	}
+
+	fn auth_header(mut self, value: String) -> Result<Self, Error> {
+		let value = HeaderValue::from_str(&value).map_err(Error::InvalidHeaderValue)?;
+		self.headers.insert(AUTHORIZATION, value);
+		Ok(self)
+	}
+
+	/// Sets the timeout for a request, covering the full round-trip including connecting,
+	/// sending the body and reading back the response (default is 60 seconds).
+	pub fn request_timeout(mut self, timeout: Duration) -> Self {
+		self.request_timeout = timeout;
+		self
+	}
+
+	/// Opt in to retrying `request`/`batch_request` on transient transport failures (connection
+	/// resets, timeouts), using a capped exponential backoff with full jitter between attempts:
+	/// `delay = min(base_delay * 2^attempt, 30s)`, sampled uniformly from `[0, delay]`.
+	///
+	/// Disabled (no retries) by default.
+	pub fn retry_policy(mut self, max_retries: u32, base_delay: Duration) -> Self {
+		self.retry_policy = Some(RetryPolicy { max_retries, base_delay });
+		self
+	}
+
 	/// Build the HTTP client with target to connect to.
 	pub fn build(self, target: impl AsRef<str>) -> Result<HttpClient, Error> {
-		let transport = HttpTransportClient::new(target, self.max_request_body_size)
+		let transport = HttpTransportClient::new(target, self.max_request_body_size, self.headers)
 			.map_err(|e| Error::TransportError(Box::new(e)))?;
-		Ok(HttpClient { transport, request_id: AtomicU64::new(0) })
+		Ok(HttpClient {
+			transport,
+			request_id: AtomicU64::new(0),
+			request_timeout: self.request_timeout,
+			retry_policy: self.retry_policy,
+		})
 	}
 }
 
 impl Default for HttpClientBuilder {
 	fn default() -> Self {
-		Self { max_request_body_size: TEN_MB_SIZE_BYTES }
+		Self {
+			max_request_body_size: TEN_MB_SIZE_BYTES,
+			headers: HeaderMap::new(),
+			request_timeout: DEFAULT_REQUEST_TIMEOUT,
+			retry_policy: None,
+		}
 	}
 }
 
@@ -46,15 +129,84 @@ pub struct HttpClient {
 	transport: HttpTransportClient,
 	/// Request ID that wraps around when overflowing.
 	request_id: AtomicU64,
+	/// Timeout applied to every request/notification/batch round-trip.
+	request_timeout: Duration,
+	/// Retry policy for transient transport failures, if enabled.
+	retry_policy: Option<RetryPolicy>,
+}
+
+impl HttpClient {
+	/// Run `fut` to completion, failing with [`Error::RequestTimeout`] if it doesn't resolve
+	/// within `self.request_timeout`.
+	async fn with_timeout<T>(&self, fut: impl std::future::Future<Output = T>) -> Result<T, Error> {
+		tokio::time::timeout(self.request_timeout, fut).await.map_err(|_| Error::RequestTimeout)
+	}
+
+	/// Send `body` and read back the response, retrying on retryable errors per
+	/// `self.retry_policy` (if set). The same serialized `body` is resent on every attempt so
+	/// response correlation via the request id keeps working.
+	async fn send_and_read_body(&self, body: Vec<u8>) -> Result<Vec<u8>, Error> {
+		let mut attempt: u32 = 0;
+		loop {
+			let outcome = match self.with_timeout(self.transport.send_and_read_body(body.clone())).await {
+				Ok(inner) => inner.map_err(|e| Error::TransportError(Box::new(e))),
+				Err(timeout_err) => Err(timeout_err),
+			};
+			let err = match outcome {
+				Ok(value) => return Ok(value),
+				Err(err) => err,
+			};
+			match self.retry_policy {
+				Some(policy) if attempt < policy.max_retries && is_retryable(&err) => {
+					tokio::time::sleep(backoff_delay(policy.base_delay, attempt)).await;
+					attempt += 1;
+				}
+				_ => return Err(err),
+			}
+		}
+	}
+}
+
+/// Whether `err` is worth retrying: any transport-level failure (connection resets etc.) or a
+/// timeout, as opposed to an error object or unparsable body sent back by the server. Every
+/// `Error::TransportError` counts, including ones that fail the same way on every attempt,
+/// such as a request body over `max_request_body_size`.
+fn is_retryable(err: &Error) -> bool {
+	matches!(err, Error::TransportError(_) | Error::RequestTimeout)
+}
+
+/// Deserialize a JSON-RPC response body (result or error object) and check that its id matches
+/// the request id we sent, shared by the borrowed- and owned-params request paths.
+fn decode_response<R: DeserializeOwned>(raw: &[u8], id: u64) -> Result<R, Error> {
+	let response: JsonRpcResponse<R> = match serde_json::from_slice(raw) {
+		Ok(response) => response,
+		Err(_) => {
+			let err: JsonRpcErrorAlloc = serde_json::from_slice(raw).map_err(Error::ParseError)?;
+			return Err(Error::Request(err));
+		}
+	};
+
+	if ids_match(id, response.id) {
+		Ok(response.result)
+	} else {
+		Err(Error::InvalidRequestId)
+	}
+}
+
+/// Capped exponential backoff with full jitter: `min(base_delay * 2^attempt, MAX_RETRY_DELAY)`,
+/// sampled uniformly from `[0, delay]`.
+fn backoff_delay(base_delay: Duration, attempt: u32) -> Duration {
+	let delay = base_delay.saturating_mul(1u32.checked_shl(attempt).unwrap_or(u32::MAX)).min(MAX_RETRY_DELAY);
+	let jittered_ms = rand::thread_rng().gen_range(0..=delay.as_millis() as u64);
+	Duration::from_millis(jittered_ms)
 }
 
 #[async_trait]
 impl Client for HttpClient {
 	async fn notification<'a>(&self, method: &'a str, params: JsonRpcParams<'a>) -> Result<(), Error> {
 		let notif = JsonRpcNotificationSer::new(method, params);
-		self.transport
-			.send(serde_json::to_string(&notif).map_err(Error::ParseError)?)
-			.await
+		self.with_timeout(self.transport.send(serde_json::to_vec(&notif).map_err(Error::ParseError)?))
+			.await?
 			.map_err(|e| Error::TransportError(Box::new(e)))
 	}
 
@@ -67,27 +219,9 @@ impl Client for HttpClient {
 		let id = self.request_id.fetch_add(1, Ordering::Relaxed);
 		let request = JsonRpcCallSer::new(Id::Number(id), method, params);
 
-		let body = self
-			.transport
-			.send_and_read_body(serde_json::to_string(&request).map_err(Error::ParseError)?)
-			.await
-			.map_err(|e| Error::TransportError(Box::new(e)))?;
-
-		let response: JsonRpcResponse<_> = match serde_json::from_slice(&body) {
-			Ok(response) => response,
-			Err(_) => {
-				let err: JsonRpcErrorAlloc = serde_json::from_slice(&body).map_err(Error::ParseError)?;
-				return Err(Error::Request(err));
-			}
-		};
-
-		let response_id = parse_request_id(response.id)?;
+		let body = self.send_and_read_body(serde_json::to_vec(&request).map_err(Error::ParseError)?).await?;
 
-		if response_id == id {
-			Ok(response.result)
-		} else {
-			Err(Error::InvalidRequestId)
-		}
+		decode_response(&body, id)
 	}
 
 	async fn batch_request<'a, R>(&self, batch: Vec<(&'a str, JsonRpcParams<'a>)>) -> Result<Vec<R>, Error>
@@ -95,22 +229,18 @@ impl Client for HttpClient {
 		R: DeserializeOwned + Default + Clone,
 	{
 		let mut batch_request = Vec::with_capacity(batch.len());
-		// NOTE(niklasad1): `ID` is not necessarily monotonically increasing.
-		let mut ordered_requests = Vec::with_capacity(batch.len());
-		let mut request_set = FnvHashMap::with_capacity_and_hasher(batch.len(), Default::default());
+		let batch_len = batch.len();
+		// NOTE(niklasad1): `ID` is not necessarily monotonically increasing, so responses are
+		// correlated back to their position via the normalized raw id rather than the id itself.
+		let mut request_set = FnvHashMap::with_capacity_and_hasher(batch_len, Default::default());
 
 		for (pos, (method, params)) in batch.into_iter().enumerate() {
 			let id = self.request_id.fetch_add(1, Ordering::SeqCst);
 			batch_request.push(JsonRpcCallSer::new(Id::Number(id), method, params));
-			ordered_requests.push(id);
-			request_set.insert(id, pos);
+			request_set.insert(normalize_id(id), pos);
 		}
 
-		let body = self
-			.transport
-			.send_and_read_body(serde_json::to_string(&batch_request).map_err(Error::ParseError)?)
-			.await
-			.map_err(|e| Error::TransportError(Box::new(e)))?;
+		let body = self.send_and_read_body(serde_json::to_vec(&batch_request).map_err(Error::ParseError)?).await?;
 
 		let rps: Vec<JsonRpcResponse<_>> = match serde_json::from_slice(&body) {
 			Ok(response) => response,
@@ -121,10 +251,10 @@ impl Client for HttpClient {
 		};
 
 		// NOTE: `R::default` is placeholder and will be replaced in loop below.
-		let mut responses = vec![R::default(); ordered_requests.len()];
+		let mut responses = vec![R::default(); batch_len];
 		for rp in rps {
-			let response_id = parse_request_id(rp.id)?;
-			let pos = match request_set.get(&response_id) {
+			let response_id = parse_raw_id(rp.id)?;
+			let pos = match request_set.get(response_id.as_ref()) {
 				Some(pos) => *pos,
 				None => return Err(Error::InvalidRequestId),
 			};
@@ -134,12 +264,242 @@ impl Client for HttpClient {
 	}
 }
 
-fn parse_request_id(raw: Option<&JsonRawValue>) -> Result<u64, Error> {
+impl HttpClient {
+	/// Perform a batch request towards the server, keeping per-request success/error outcomes
+	/// instead of bailing out as soon as one element of the response array is an error object.
+	///
+	/// A JSON-RPC batch response legitimately mixes result and error objects across its ids, so
+	/// the top-level `Err` here is reserved for transport/parse failures of the whole body; any
+	/// individual request failing is reported through the corresponding `Err` entry in the
+	/// returned `Vec`. A position is `None` if the server never sent back a response for that
+	/// id at all (itself a spec violation, but one that shouldn't sink the rest of the batch).
+	/// An element that can't be placed, i.e. whose id is missing or matches no request in the
+	/// batch, fails the whole call with [`Error::InvalidRequestId`]; an element that is neither a
+	/// result nor an error object fails it with [`Error::ParseError`].
+	pub async fn batch_request_partial<'a, R>(
+		&self,
+		batch: Vec<(&'a str, JsonRpcParams<'a>)>,
+	) -> Result<Vec<Option<Result<R, JsonRpcErrorAlloc>>>, Error>
+	where
+		R: DeserializeOwned,
+	{
+		let mut batch_request = Vec::with_capacity(batch.len());
+		let batch_len = batch.len();
+		let mut request_set = FnvHashMap::with_capacity_and_hasher(batch_len, Default::default());
+
+		for (pos, (method, params)) in batch.into_iter().enumerate() {
+			let id = self.request_id.fetch_add(1, Ordering::SeqCst);
+			batch_request.push(JsonRpcCallSer::new(Id::Number(id), method, params));
+			request_set.insert(normalize_id(id), pos);
+		}
+
+		let body = self.send_and_read_body(serde_json::to_vec(&batch_request).map_err(Error::ParseError)?).await?;
+
+		let raw_rps: Vec<&JsonRawValue> = serde_json::from_slice(&body).map_err(Error::ParseError)?;
+
+		decode_batch_responses(raw_rps, batch_len, &request_set)
+	}
+
+	/// Perform a request with owned, directly serializable params instead of borrowing a
+	/// `JsonRpcParams<'a>`. Serializes straight into a byte buffer via `serde_json::to_vec`,
+	/// mirroring ethers' `request<T: Serialize>` and letting callers who build params
+	/// dynamically avoid an awkward borrow lifetime.
+	pub async fn request_with_params<R, P>(&self, method: &str, params: P) -> Result<R, Error>
+	where
+		R: DeserializeOwned,
+		P: serde::Serialize,
+	{
+		let id = self.request_id.fetch_add(1, Ordering::Relaxed);
+		let call = OwnedCall { jsonrpc: TWO_POINT_ZERO, id, method, params };
+		let body = self.send_and_read_body(serde_json::to_vec(&call).map_err(Error::ParseError)?).await?;
+		decode_response(&body, id)
+	}
+
+	/// Fire-and-forget notification with owned, directly serializable params. See
+	/// [`HttpClient::request_with_params`].
+	pub async fn notification_with_params<P>(&self, method: &str, params: P) -> Result<(), Error>
+	where
+		P: serde::Serialize,
+	{
+		let notif = OwnedNotification { jsonrpc: TWO_POINT_ZERO, method, params };
+		let body = serde_json::to_vec(&notif).map_err(Error::ParseError)?;
+		self.with_timeout(self.transport.send(body)).await?.map_err(|e| Error::TransportError(Box::new(e)))
+	}
+}
+
+/// JSON-RPC protocol version tag, always `"2.0"`.
+const TWO_POINT_ZERO: &str = "2.0";
+
+/// A JSON-RPC call whose `params` are serialized directly via `P: Serialize`, used by
+/// [`HttpClient::request_with_params`] instead of the borrowed `JsonRpcCallSer`.
+#[derive(serde::Serialize)]
+struct OwnedCall<'a, P> {
+	jsonrpc: &'static str,
+	id: u64,
+	method: &'a str,
+	params: P,
+}
+
+/// The notification counterpart of [`OwnedCall`], used by
+/// [`HttpClient::notification_with_params`].
+#[derive(serde::Serialize)]
+struct OwnedNotification<'a, P> {
+	jsonrpc: &'static str,
+	method: &'a str,
+	params: P,
+}
+
+/// Borrowed view of just the `id` field of a raw JSON-RPC response element, used to correlate a
+/// batch element back to its request before committing to parsing it as either a result or an
+/// error object (whose owned and borrowed `id` types don't unify).
+#[derive(serde::Deserialize)]
+struct RawIdView<'a> {
+	#[serde(borrow)]
+	id: Option<&'a JsonRawValue>,
+}
+
+/// Decode each element of a raw batch response array into its per-position outcome, correlating
+/// ids against `request_set` (see [`HttpClient::batch_request_partial`]). Split out from
+/// `batch_request_partial` so it can be unit-tested without a live transport.
+fn decode_batch_responses<R: DeserializeOwned>(
+	raw_rps: Vec<&JsonRawValue>,
+	batch_len: usize,
+	request_set: &FnvHashMap<String, usize>,
+) -> Result<Vec<Option<Result<R, JsonRpcErrorAlloc>>>, Error> {
+	let mut responses: Vec<Option<Result<R, JsonRpcErrorAlloc>>> = (0..batch_len).map(|_| None).collect();
+	for raw in raw_rps {
+		// Peek the id without committing to either the result or error shape, since the two
+		// don't unify: `JsonRpcResponse::id` borrows from `raw`, while `JsonRpcErrorAlloc::id`
+		// is owned (it's returned out of `request()` past the response body elsewhere).
+		let RawIdView { id } = serde_json::from_str(raw.get()).map_err(Error::ParseError)?;
+		let response_id = parse_raw_id(id)?;
+		let pos = match request_set.get(response_id.as_ref()) {
+			Some(pos) => *pos,
+			None => return Err(Error::InvalidRequestId),
+		};
+
+		let outcome = match serde_json::from_str::<JsonRpcResponse<R>>(raw.get()) {
+			Ok(rp) => Ok(rp.result),
+			Err(_) => {
+				let err: JsonRpcErrorAlloc = serde_json::from_str(raw.get()).map_err(Error::ParseError)?;
+				Err(err)
+			}
+		};
+		responses[pos] = Some(outcome);
+	}
+	Ok(responses)
+}
+
+/// Normalize a `u64` request id into the textual form used to correlate it against a raw
+/// response id, so that a server echoing `1` back as the string `"1"` still matches.
+fn normalize_id(id: u64) -> String {
+	id.to_string()
+}
+
+/// Extract the normalized textual value of a raw JSON-RPC id, stripping the surrounding quotes
+/// if the server sent it back as a JSON string instead of a JSON number.
+fn parse_raw_id(raw: Option<&JsonRawValue>) -> Result<std::borrow::Cow<'_, str>, Error> {
 	match raw {
 		None => Err(Error::InvalidRequestId),
 		Some(id) => {
-			let id = serde_json::from_str(id.get()).map_err(Error::ParseError)?;
-			Ok(id)
+			let text = id.get().trim();
+			match text.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+				Some(unquoted) => Ok(std::borrow::Cow::Borrowed(unquoted)),
+				None => Ok(std::borrow::Cow::Borrowed(text)),
+			}
+		}
+	}
+}
+
+/// Check whether a raw response id matches the `u64` id we sent, tolerant of servers that
+/// echo ids as a JSON string rather than a JSON number (many don't follow the spec strictly).
+fn ids_match(sent: u64, received: Option<&JsonRawValue>) -> bool {
+	match parse_raw_id(received) {
+		Ok(received) => received == normalize_id(sent),
+		Err(_) => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn raw(json: &str) -> Box<JsonRawValue> {
+		JsonRawValue::from_string(json.to_owned()).unwrap()
+	}
+
+	// chunk0-1: a server echoing the id back as a JSON string must still correlate against the
+	// numeric id we sent.
+	#[test]
+	fn ids_match_numeric_sent_against_string_received() {
+		let received = raw("\"1\"");
+		assert!(ids_match(1, Some(&received)));
+		assert!(!ids_match(2, Some(&received)));
+	}
+
+	#[test]
+	fn ids_match_numeric_sent_against_numeric_received() {
+		let received = raw("1");
+		assert!(ids_match(1, Some(&received)));
+	}
+
+	#[test]
+	fn ids_match_none_never_matches() {
+		assert!(!ids_match(1, None));
+	}
+
+	// chunk0-2: a batch response mixing result and error objects, plus one id that never gets a
+	// response, should decode into per-position Some(Ok)/Some(Err)/None rather than erroring out.
+	#[test]
+	fn decode_batch_responses_mixes_result_error_and_missing() {
+		let mut request_set = FnvHashMap::default();
+		request_set.insert(normalize_id(0), 0);
+		request_set.insert(normalize_id(1), 1);
+		request_set.insert(normalize_id(2), 2);
+
+		let ok_elem = raw(r#"{"jsonrpc":"2.0","id":0,"result":42}"#);
+		let err_elem = raw(r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32000,"message":"boom"}}"#);
+		let raw_rps = vec![ok_elem.as_ref(), err_elem.as_ref()];
+
+		let decoded: Vec<Option<Result<u64, JsonRpcErrorAlloc>>> = match decode_batch_responses(raw_rps, 3, &request_set) {
+			Ok(decoded) => decoded,
+			Err(_) => panic!("expected decode_batch_responses to succeed"),
+		};
+
+		assert!(matches!(decoded[0], Some(Ok(42))));
+		assert!(matches!(decoded[1], Some(Err(_))));
+		assert!(decoded[2].is_none());
+	}
+
+	#[test]
+	fn decode_batch_responses_rejects_unknown_id() {
+		let request_set = FnvHashMap::default();
+		let elem = raw(r#"{"jsonrpc":"2.0","id":7,"result":1}"#);
+
+		let decoded = decode_batch_responses::<u64>(vec![elem.as_ref()], 1, &request_set);
+		assert!(matches!(decoded, Err(Error::InvalidRequestId)));
+	}
+
+	// chunk0-5: capped exponential backoff with full jitter.
+	#[test]
+	fn backoff_delay_is_zero_when_base_delay_is_zero() {
+		assert_eq!(backoff_delay(Duration::ZERO, 0), Duration::ZERO);
+		assert_eq!(backoff_delay(Duration::ZERO, 10), Duration::ZERO);
+	}
+
+	#[test]
+	fn backoff_delay_never_exceeds_cap() {
+		for attempt in [0u32, 1, 2, 5, 10, 31, 32, u32::MAX] {
+			let delay = backoff_delay(Duration::from_millis(100), attempt);
+			assert!(delay <= MAX_RETRY_DELAY, "attempt {attempt} produced {delay:?} > cap");
 		}
 	}
+
+	#[test]
+	fn backoff_delay_bounded_by_exponential_ceiling_before_capping() {
+		// base_delay * 2^attempt stays under the cap for the first couple of attempts, so the
+		// jittered delay should respect that (still smaller) ceiling.
+		assert!(backoff_delay(Duration::from_millis(100), 0) <= Duration::from_millis(100));
+		assert!(backoff_delay(Duration::from_millis(100), 1) <= Duration::from_millis(200));
+	}
 }
@@ -0,0 +1,87 @@
+use crate::Error;
+use http::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use hyper::client::{Client, HttpConnector};
+use hyper::{Body, Request, Uri};
+
+const CONTENT_TYPE_JSON: &str = "application/json";
+
+/// Raw HTTP transport used by [`crate::HttpClient`] to send/receive JSON-RPC request and
+/// response bodies. Doesn't know anything about JSON-RPC framing; just moves bytes.
+#[derive(Debug)]
+pub(crate) struct HttpTransportClient {
+	/// Target to connect to.
+	target: Uri,
+	/// Underlying HTTP client.
+	client: Client<HttpConnector, Body>,
+	/// Max size of a request body in bytes, checked before sending.
+	max_request_body_size: u32,
+	/// Headers sent with every request, on top of the ones set internally (`content-type`
+	/// etc). These take precedence on conflicts.
+	headers: HeaderMap,
+}
+
+impl HttpTransportClient {
+	/// Initializes a new HTTP transport targeting `target`.
+	pub(crate) fn new(target: impl AsRef<str>, max_request_body_size: u32, headers: HeaderMap) -> Result<Self, Error> {
+		let target: Uri =
+			target.as_ref().parse().map_err(|e| Error::TransportError(Box::new(TransportError::Url(format!("{}", e)))))?;
+		match target.scheme_str() {
+			Some("http") | Some("https") => (),
+			scheme => {
+				return Err(Error::TransportError(Box::new(TransportError::Url(format!(
+					"URL scheme not supported, expects 'http' or 'https', got: {:?}",
+					scheme
+				)))))
+			}
+		}
+		Ok(Self { target, client: Client::new(), max_request_body_size, headers })
+	}
+
+	/// Build a POST request carrying `body`, setting the default headers first and then
+	/// layering `self.headers` on top so custom headers win on conflicts.
+	fn build_request(&self, body: Vec<u8>) -> Result<Request<Body>, TransportError> {
+		let mut request = Request::post(&self.target)
+			.header(CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE_JSON))
+			.body(Body::from(body))
+			.map_err(|e| TransportError::Http(e.to_string()))?;
+		request.headers_mut().extend(self.headers.clone());
+		Ok(request)
+	}
+
+	async fn inner_send(&self, body: Vec<u8>) -> Result<hyper::Response<Body>, Error> {
+		if body.len() > self.max_request_body_size as usize {
+			return Err(Error::TransportError(Box::new(TransportError::RequestTooLarge)));
+		}
+		let request = self.build_request(body).map_err(|e| Error::TransportError(Box::new(e)))?;
+		self.client.request(request).await.map_err(|e| Error::TransportError(Box::new(TransportError::Hyper(e))))
+	}
+
+	/// Send a notification, discarding the response body.
+	pub(crate) async fn send(&self, body: Vec<u8>) -> Result<(), Error> {
+		let _response = self.inner_send(body).await?;
+		Ok(())
+	}
+
+	/// Send a request and read back the full response body.
+	pub(crate) async fn send_and_read_body(&self, body: Vec<u8>) -> Result<Vec<u8>, Error> {
+		let response = self.inner_send(body).await?;
+		let body = hyper::body::to_bytes(response.into_body())
+			.await
+			.map_err(|e| Error::TransportError(Box::new(TransportError::Hyper(e))))?;
+		Ok(body.to_vec())
+	}
+}
+
+/// Errors that can occur within [`HttpTransportClient`], boxed into [`Error::TransportError`]
+/// at the call site.
+#[derive(Debug, thiserror::Error)]
+enum TransportError {
+	#[error("invalid URL: {0}")]
+	Url(String),
+	#[error("request body exceeds max_request_body_size")]
+	RequestTooLarge,
+	#[error("HTTP error: {0}")]
+	Http(String),
+	#[error("HTTP transport error: {0}")]
+	Hyper(#[from] hyper::Error),
+}